@@ -1,10 +1,28 @@
-use crate::{DeclId, ModuleId, OverlayId, Type, Value, VarId};
+use crate::{DeclId, ModuleId, OverlayId, Span, Type, Value, VarId};
 use std::borrow::Borrow;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::hash::{Hash, Hasher};
 
 pub static DEFAULT_OVERLAY_NAME: &str = "zero";
 
+/// Default capacity of the decl resolution cache on a [`ScopeFrame`].
+const DEFAULT_DECL_CACHE_CAPACITY: usize = 512;
+
+/// The visibility of a decl as recorded by a single overlay.
+///
+/// Unlike a plain bool, this distinguishes "this overlay has never heard of the decl" from
+/// "this overlay has explicitly made the decl visible", so callers walking a stack of overlays
+/// can tell whether to trust the answer or defer to the next overlay down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeclVisibility {
+    /// Explicitly made visible (e.g. via `use`) in this overlay.
+    Visible,
+    /// Explicitly hidden (e.g. via `hide`) in this overlay.
+    Hidden,
+    /// Never mentioned in this overlay; resolution should defer to the next overlay down.
+    Unknown,
+}
+
 /// Tells whether a decl is visible or not
 #[derive(Debug, Clone)]
 pub struct Visibility {
@@ -18,8 +36,21 @@ impl Visibility {
         }
     }
 
+    /// Look up this overlay's opinion of `decl_id`, distinguishing "never mentioned" from
+    /// "explicitly visible" so callers can decide whether to defer to a lower overlay.
+    pub fn get_decl_id_visibility(&self, decl_id: &DeclId) -> DeclVisibility {
+        match self.decl_ids.get(decl_id) {
+            Some(true) => DeclVisibility::Visible,
+            Some(false) => DeclVisibility::Hidden,
+            None => DeclVisibility::Unknown,
+        }
+    }
+
     pub fn is_decl_id_visible(&self, decl_id: &DeclId) -> bool {
-        *self.decl_ids.get(decl_id).unwrap_or(&true) // by default it's visible
+        !matches!(
+            self.get_decl_id_visibility(decl_id),
+            DeclVisibility::Hidden
+        )
     }
 
     pub fn hide_decl_id(&mut self, decl_id: &DeclId) {
@@ -45,6 +76,178 @@ impl Visibility {
     }
 }
 
+/// A single reversible mutation recorded while a transaction is open.
+///
+/// Each variant carries the prior value of whatever it touched (`None` if the key didn't exist
+/// before), so undoing it restores the exact pre-transaction state, whether the mutation
+/// introduced a brand new key or overwrote an existing one.
+#[derive(Debug, Clone)]
+enum JournalOp {
+    /// A `(name, input)` key in an overlay's `decls` was set to `decl_id`; carries the prior
+    /// `DeclId` for that key and the prior `usages` entry for `decl_id` itself, so both the decl
+    /// table and its doc comments can be restored exactly.
+    SetDecl(OverlayId, Vec<u8>, Type, DeclId, Option<DeclId>, Option<Vec<Span>>),
+    /// A var name in an overlay's `vars` was set; carries the prior `VarId`.
+    SetVar(OverlayId, Vec<u8>, Option<VarId>),
+    /// A module name in an overlay's `modules` was set; carries the prior `ModuleId`.
+    SetModule(OverlayId, Vec<u8>, Option<ModuleId>),
+    /// A var id in an overlay's `constants` was set; carries the prior `Value`.
+    SetConstant(OverlayId, VarId, Option<Value>),
+    /// A decl's visibility was set; carries the prior value so it can be restored exactly, and
+    /// whether the change added the decl to `change_log.hidden_decls` (so `change_log.hidden_decls`
+    /// can be kept in sync on undo).
+    SetVisibility(OverlayId, DeclId, Option<bool>, bool),
+}
+
+/// Key for the decl resolution cache: a decl is resolved by name and requested input type.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct DeclCacheKey(Vec<u8>, Type);
+
+/// A slot in [`DeclCache`]'s recency list, threaded together with plain indices rather than
+/// pointers since the cache owns every node it ever hands out.
+#[derive(Debug, Clone)]
+struct DeclCacheNode {
+    key: DeclCacheKey,
+    value: Option<DeclId>,
+    prev: Option<usize>,
+    next: Option<usize>,
+}
+
+/// A small bounded LRU cache memoizing `(name, input type) -> DeclId` resolution across the
+/// active overlay chain. Negative results (`None`) are cached too, since a nonexistent decl is
+/// looked up just as often as an existing one.
+///
+/// Recency is tracked with an intrusive doubly linked list threaded through `nodes` (a slab:
+/// freed slots are recycled via `free` instead of shifting the vec), so `get`/`insert`/`touch`
+/// are O(1) instead of scanning for a key's position on every cache hit — this is the hot path
+/// where large scripts resolve the same commands thousands of times.
+#[derive(Debug, Clone)]
+struct DeclCache {
+    capacity: usize,
+    index: HashMap<DeclCacheKey, usize>,
+    nodes: Vec<DeclCacheNode>,
+    free: Vec<usize>,
+    most_recent: Option<usize>,
+    least_recent: Option<usize>,
+}
+
+impl DeclCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            index: HashMap::new(),
+            nodes: Vec::new(),
+            free: Vec::new(),
+            most_recent: None,
+            least_recent: None,
+        }
+    }
+
+    fn get(&mut self, key: &DeclCacheKey) -> Option<Option<DeclId>> {
+        let slot = *self.index.get(key)?;
+        self.touch(slot);
+        Some(self.nodes[slot].value)
+    }
+
+    fn insert(&mut self, key: DeclCacheKey, value: Option<DeclId>) {
+        if let Some(&slot) = self.index.get(&key) {
+            self.nodes[slot].value = value;
+            self.touch(slot);
+            return;
+        }
+
+        let slot = if let Some(slot) = self.free.pop() {
+            self.nodes[slot] = DeclCacheNode {
+                key: key.clone(),
+                value,
+                prev: None,
+                next: None,
+            };
+            slot
+        } else {
+            self.nodes.push(DeclCacheNode {
+                key: key.clone(),
+                value,
+                prev: None,
+                next: None,
+            });
+            self.nodes.len() - 1
+        };
+
+        self.index.insert(key, slot);
+        self.push_front(slot);
+
+        if self.index.len() > self.capacity {
+            self.evict_least_recent();
+        }
+    }
+
+    fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+
+        while self.index.len() > self.capacity {
+            self.evict_least_recent();
+        }
+    }
+
+    fn clear(&mut self) {
+        self.index.clear();
+        self.nodes.clear();
+        self.free.clear();
+        self.most_recent = None;
+        self.least_recent = None;
+    }
+
+    /// Move `slot` to the front of the recency list (most recently used).
+    fn touch(&mut self, slot: usize) {
+        if self.most_recent == Some(slot) {
+            return;
+        }
+
+        self.unlink(slot);
+        self.push_front(slot);
+    }
+
+    fn unlink(&mut self, slot: usize) {
+        let (prev, next) = (self.nodes[slot].prev, self.nodes[slot].next);
+
+        match prev {
+            Some(prev) => self.nodes[prev].next = next,
+            None => self.most_recent = next,
+        }
+
+        match next {
+            Some(next) => self.nodes[next].prev = prev,
+            None => self.least_recent = prev,
+        }
+
+        self.nodes[slot].prev = None;
+        self.nodes[slot].next = None;
+    }
+
+    fn push_front(&mut self, slot: usize) {
+        self.nodes[slot].next = self.most_recent;
+
+        if let Some(old_most_recent) = self.most_recent {
+            self.nodes[old_most_recent].prev = Some(slot);
+        }
+
+        self.most_recent = Some(slot);
+
+        if self.least_recent.is_none() {
+            self.least_recent = Some(slot);
+        }
+    }
+
+    fn evict_least_recent(&mut self) {
+        if let Some(slot) = self.least_recent {
+            self.unlink(slot);
+            self.index.remove(&self.nodes[slot].key);
+            self.free.push(slot);
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ScopeFrame {
     /// List of both active and inactive overlays in this ScopeFrame.
@@ -55,14 +258,25 @@ pub struct ScopeFrame {
 
     /// List of currently active overlays.
     ///
-    /// Order is significant: The last item points at the last activated overlay.
-    pub active_overlays: Vec<OverlayId>,
+    /// Order is significant: The last item points at the last activated overlay. Private so
+    /// [`ScopeFrame::activate_overlay`]/[`ScopeFrame::remove_overlay`] are the only way to mutate
+    /// it — both clear the decl resolution cache, which a direct `push`/`retain` would bypass.
+    active_overlays: Vec<OverlayId>,
 
-    /// Removed overlays from previous scope frames / permanent state
-    pub removed_overlays: Vec<Vec<u8>>,
+    /// Removed overlays from previous scope frames / permanent state. Private for the same reason
+    /// as `active_overlays`: mutation must go through [`ScopeFrame::remove_overlay`].
+    removed_overlays: Vec<Vec<u8>>,
 
     /// temporary storage for predeclarations
     pub predecls: HashMap<Vec<u8>, DeclId>,
+
+    /// Stack of open transactions, innermost last. Each transaction owns a journal of the
+    /// reversible operations performed since it was started.
+    transactions: Vec<Vec<JournalOp>>,
+
+    /// Memoizes `get_decl` resolutions across the active overlay chain. Invalidated whenever a
+    /// decl is inserted or hidden/used, or the active overlay chain changes.
+    decl_cache: DeclCache,
 }
 
 impl ScopeFrame {
@@ -72,6 +286,8 @@ impl ScopeFrame {
             active_overlays: vec![],
             removed_overlays: vec![],
             predecls: HashMap::new(),
+            transactions: vec![],
+            decl_cache: DeclCache::new(DEFAULT_DECL_CACHE_CAPACITY),
         }
     }
 
@@ -81,9 +297,271 @@ impl ScopeFrame {
             active_overlays: vec![0],
             removed_overlays: vec![],
             predecls: HashMap::new(),
+            transactions: vec![],
+            decl_cache: DeclCache::new(DEFAULT_DECL_CACHE_CAPACITY),
+        }
+    }
+
+    /// Open a new, nestable transaction. Mutations performed through
+    /// [`ScopeFrame::insert_decl`], [`ScopeFrame::insert_var`], [`ScopeFrame::insert_module`],
+    /// [`ScopeFrame::hide_decl_id`], and [`ScopeFrame::use_decl_id`] are journaled until the
+    /// transaction is committed or rolled back.
+    pub fn start_transaction(&mut self) {
+        self.transactions.push(Vec::new());
+    }
+
+    /// Commit the innermost transaction. If there is an enclosing transaction, its journal is
+    /// folded into that one so an outer rollback can still undo it; if this was the outermost
+    /// transaction, its journal is simply discarded and the changes become permanent.
+    pub fn commit_transaction(&mut self) {
+        if let Some(journal) = self.transactions.pop() {
+            if let Some(parent) = self.transactions.last_mut() {
+                parent.extend(journal);
+            }
+        }
+    }
+
+    /// Roll back the innermost transaction, undoing every operation it recorded. Entries that
+    /// existed before the transaction started are left untouched. The decl resolution cache is
+    /// cleared, since it may hold resolutions computed against the now-reverted state.
+    pub fn rollback_transaction(&mut self) {
+        if let Some(journal) = self.transactions.pop() {
+            for op in journal.into_iter().rev() {
+                self.undo_journal_op(op);
+            }
+
+            self.decl_cache.clear();
+        }
+    }
+
+    fn journal(&mut self, op: JournalOp) {
+        if let Some(journal) = self.transactions.last_mut() {
+            journal.push(op);
         }
     }
 
+    fn undo_journal_op(&mut self, op: JournalOp) {
+        match op {
+            JournalOp::SetDecl(overlay_id, name, input, decl_id, prior, prior_comments) => {
+                let key = (name, input);
+                let overlay = self.get_overlay_mut(overlay_id);
+
+                match prior {
+                    Some(prior_decl_id) => {
+                        overlay.decls.insert(key.clone(), prior_decl_id);
+                        overlay.change_log.overridden_decls.remove(&key);
+                    }
+                    None => {
+                        overlay.decls.remove(&key);
+                        overlay.change_log.added_decls.remove(&key);
+                    }
+                }
+
+                match prior_comments {
+                    Some(comments) => {
+                        overlay.usages.insert(decl_id, comments);
+                    }
+                    None => {
+                        overlay.usages.remove(&decl_id);
+                    }
+                }
+            }
+            JournalOp::SetVar(overlay_id, name, prior) => {
+                let overlay = self.get_overlay_mut(overlay_id);
+
+                match prior {
+                    Some(var_id) => {
+                        overlay.vars.insert(name.clone(), var_id);
+                        overlay.change_log.overridden_vars.remove(&name);
+                    }
+                    None => {
+                        overlay.vars.remove(&name);
+                        overlay.change_log.added_vars.remove(&name);
+                    }
+                }
+            }
+            JournalOp::SetModule(overlay_id, name, prior) => {
+                let overlay = self.get_overlay_mut(overlay_id);
+
+                match prior {
+                    Some(module_id) => {
+                        overlay.modules.insert(name.clone(), module_id);
+                        overlay.change_log.overridden_modules.remove(&name);
+                    }
+                    None => {
+                        overlay.modules.remove(&name);
+                        overlay.change_log.added_modules.remove(&name);
+                    }
+                }
+            }
+            JournalOp::SetConstant(overlay_id, var_id, prior) => {
+                let overlay = self.get_overlay_mut(overlay_id);
+
+                match prior {
+                    Some(value) => {
+                        overlay.constants.insert(var_id, value);
+                        overlay.change_log.overridden_constants.remove(&var_id);
+                    }
+                    None => {
+                        overlay.constants.remove(&var_id);
+                        overlay.change_log.added_constants.remove(&var_id);
+                    }
+                }
+            }
+            JournalOp::SetVisibility(overlay_id, decl_id, prior, newly_hidden) => {
+                let overlay = self.get_overlay_mut(overlay_id);
+
+                match prior {
+                    Some(visible) => {
+                        overlay.visibility.decl_ids.insert(decl_id, visible);
+                    }
+                    None => {
+                        overlay.visibility.decl_ids.remove(&decl_id);
+                    }
+                }
+
+                if newly_hidden {
+                    overlay.change_log.hidden_decls.remove(&decl_id);
+                }
+            }
+        }
+    }
+
+    /// Insert a decl into `overlay_id`, journaling the prior `DeclId` (if any) so a rollback can
+    /// restore it exactly, whether this introduced a brand new key or overwrote an existing one.
+    /// Also journals the prior `usages` entry for `decl_id`, since the parser can retry a block
+    /// and reuse the same `DeclId` with no or different comments, and a rollback must not leave a
+    /// stale doc comment behind for the next attempt.
+    pub fn insert_decl(
+        &mut self,
+        overlay_id: OverlayId,
+        name: Vec<u8>,
+        input: Type,
+        decl_id: DeclId,
+        comments: Option<Vec<Span>>,
+    ) -> Option<DeclId> {
+        let key = (name, input);
+        let (prior, prior_comments) = self.get_overlay_mut(overlay_id).insert_decl(
+            key.0.clone(),
+            key.1.clone(),
+            decl_id,
+            comments,
+        );
+
+        self.journal(JournalOp::SetDecl(
+            overlay_id,
+            key.0,
+            key.1,
+            decl_id,
+            prior,
+            prior_comments,
+        ));
+        self.decl_cache.clear();
+
+        prior
+    }
+
+    /// Resolve `name`/`input` to a `DeclId` through [`ScopeFrame::get_decl`] first, so a decl
+    /// hidden by a higher overlay doesn't leak its comments through a lower overlay that still
+    /// considers it visible, then fetch the comments recorded for that specific decl.
+    pub fn get_decl_comments(&self, name: &[u8], input: &Type) -> Option<&[Span]> {
+        let decl_id = self.get_decl(name, input)?;
+
+        for &overlay_id in self.active_overlays.iter().rev() {
+            if let Some(comments) = self.get_overlay(overlay_id).get_comments_for_decl_id(&decl_id)
+            {
+                return Some(comments);
+            }
+        }
+
+        None
+    }
+
+    /// Insert a var into `overlay_id`, journaling the prior `VarId` (if any) so a rollback can
+    /// restore it exactly, whether this introduced a brand new name or overwrote an existing one.
+    pub fn insert_var(&mut self, overlay_id: OverlayId, name: Vec<u8>, var_id: VarId) -> Option<VarId> {
+        let prior = self
+            .get_overlay_mut(overlay_id)
+            .insert_var(name.clone(), var_id);
+
+        self.journal(JournalOp::SetVar(overlay_id, name, prior));
+
+        prior
+    }
+
+    /// Insert a module into `overlay_id`, journaling the prior `ModuleId` (if any) so a rollback
+    /// can restore it exactly, whether this introduced a brand new name or overwrote an existing
+    /// one.
+    pub fn insert_module(
+        &mut self,
+        overlay_id: OverlayId,
+        name: Vec<u8>,
+        module_id: ModuleId,
+    ) -> Option<ModuleId> {
+        let prior = self
+            .get_overlay_mut(overlay_id)
+            .insert_module(name.clone(), module_id);
+
+        self.journal(JournalOp::SetModule(overlay_id, name, prior));
+
+        prior
+    }
+
+    /// Insert a constant into `overlay_id`, journaling the prior `Value` (if any) so a rollback
+    /// can restore it exactly, whether this introduced a brand new var id or overwrote an
+    /// existing one.
+    pub fn insert_constant(
+        &mut self,
+        overlay_id: OverlayId,
+        var_id: VarId,
+        value: Value,
+    ) -> Option<Value> {
+        let prior = self.get_overlay_mut(overlay_id).insert_constant(var_id, value);
+
+        self.journal(JournalOp::SetConstant(overlay_id, var_id, prior.clone()));
+
+        prior
+    }
+
+    /// Hide `decl_id` in `overlay_id`, journaling the prior visibility so it can be restored.
+    pub fn hide_decl_id(&mut self, overlay_id: OverlayId, decl_id: DeclId) {
+        let prior = self
+            .get_overlay(overlay_id)
+            .visibility
+            .decl_ids
+            .get(&decl_id)
+            .copied();
+        let newly_hidden = !self
+            .get_overlay(overlay_id)
+            .change_log
+            .hidden_decls
+            .contains(&decl_id);
+
+        self.get_overlay_mut(overlay_id).hide_decl_id(&decl_id);
+
+        self.journal(JournalOp::SetVisibility(overlay_id, decl_id, prior, newly_hidden));
+        self.decl_cache.clear();
+    }
+
+    /// Mark `decl_id` visible in `overlay_id`, journaling the prior visibility so it can be
+    /// restored.
+    pub fn use_decl_id(&mut self, overlay_id: OverlayId, decl_id: DeclId) {
+        let prior = self
+            .get_overlay(overlay_id)
+            .visibility
+            .decl_ids
+            .get(&decl_id)
+            .copied();
+
+        self.get_overlay_mut(overlay_id)
+            .visibility
+            .use_decl_id(&decl_id);
+
+        self.decl_cache.clear();
+
+        self.journal(JournalOp::SetVisibility(overlay_id, decl_id, prior, false));
+    }
+
     pub fn get_var(&self, var_name: &[u8]) -> Option<&VarId> {
         for overlay_id in self.active_overlays.iter().rev() {
             if let Some(var_id) = self
@@ -158,6 +636,88 @@ impl ScopeFrame {
             .1
     }
 
+    /// Resolve `name`/`input` to a `DeclId` by walking the active overlays from the most
+    /// recently activated down, the same order [`ScopeFrame::get_var`] uses. A decl found in one
+    /// overlay can still be masked by a `Hidden` verdict recorded in a higher overlay, even if a
+    /// lower overlay considers it `Visible`.
+    pub fn get_decl(&self, name: &[u8], input: &Type) -> Option<DeclId> {
+        for &overlay_id in self.active_overlays.iter().rev() {
+            if let Some(decl_id) = self.get_overlay(overlay_id).get_decl(name, input) {
+                return match self.resolve_decl_visibility(&decl_id) {
+                    DeclVisibility::Hidden => None,
+                    DeclVisibility::Visible | DeclVisibility::Unknown => Some(decl_id),
+                };
+            }
+        }
+
+        None
+    }
+
+    /// Like [`ScopeFrame::get_decl`], but memoized across the active overlay chain. Targets the
+    /// hot path where large scripts resolve the same commands thousands of times; call sites
+    /// that don't run in a loop can keep using `get_decl` directly.
+    pub fn get_decl_cached(&mut self, name: &[u8], input: &Type) -> Option<DeclId> {
+        let key = DeclCacheKey(name.to_vec(), input.clone());
+
+        if let Some(decl_id) = self.decl_cache.get(&key) {
+            return decl_id;
+        }
+
+        let decl_id = self.get_decl(name, input);
+        self.decl_cache.insert(key, decl_id);
+        decl_id
+    }
+
+    /// Set the capacity of the decl resolution cache used by [`ScopeFrame::get_decl_cached`].
+    pub fn set_decl_cache_capacity(&mut self, capacity: usize) {
+        self.decl_cache.set_capacity(capacity);
+    }
+
+    /// Drop every memoized entry in the decl resolution cache. [`ScopeFrame::activate_overlay`]
+    /// and [`ScopeFrame::remove_overlay`] already call this for you; call it directly only if you
+    /// mutate `active_overlays`/`removed_overlays` some other way.
+    pub fn clear_decl_cache(&mut self) {
+        self.decl_cache.clear();
+    }
+
+    /// Activate `overlay_id`, making it the most recently activated overlay. This is the
+    /// sanctioned way to extend the active overlay chain: it clears the decl resolution cache for
+    /// you, so a resolution memoized against the old chain can't leak into the new one.
+    pub fn activate_overlay(&mut self, overlay_id: OverlayId) {
+        self.active_overlays.push(overlay_id);
+        self.clear_decl_cache();
+    }
+
+    /// Deactivate the overlay named `name` and record it as removed. Like
+    /// [`ScopeFrame::activate_overlay`], this is the sanctioned way to shrink the active overlay
+    /// chain, clearing the decl resolution cache as part of the same call.
+    pub fn remove_overlay(&mut self, name: Vec<u8>) {
+        if let Some(overlay_id) = self.find_active_overlay(&name) {
+            self.active_overlays.retain(|id| *id != overlay_id);
+        }
+
+        self.removed_overlays.push(name);
+        self.clear_decl_cache();
+    }
+
+    /// Walk the active overlays from the most recently activated down, returning the first
+    /// explicit opinion (`Visible` or `Hidden`) any of them has about `decl_id`. `Unknown`
+    /// overlays are skipped so the walk continues to the next one down.
+    fn resolve_decl_visibility(&self, decl_id: &DeclId) -> DeclVisibility {
+        for &overlay_id in self.active_overlays.iter().rev() {
+            match self
+                .get_overlay(overlay_id)
+                .visibility
+                .get_decl_id_visibility(decl_id)
+            {
+                DeclVisibility::Unknown => continue,
+                verdict => return verdict,
+            }
+        }
+
+        DeclVisibility::Unknown
+    }
+
     pub fn find_overlay(&self, name: &[u8]) -> Option<OverlayId> {
         self.overlays.iter().position(|(n, _)| n == name)
     }
@@ -178,14 +738,46 @@ impl ScopeFrame {
 
 #[derive(Debug, Clone)]
 pub struct OverlayFrame {
-    pub vars: HashMap<Vec<u8>, VarId>,
-    pub constants: HashMap<VarId, Value>,
+    // `vars`/`constants`/`decls`/`modules`/`visibility` are private so they are only mutated
+    // through methods. Only `ScopeFrame::insert_*`/`hide_decl_id`/`use_decl_id` journal the
+    // change; calling the `OverlayFrame` methods directly (e.g. via `ScopeFrame::get_overlay_mut`)
+    // is not journaled, so a transaction rollback does not undo it.
+    vars: HashMap<Vec<u8>, VarId>,
+    constants: HashMap<VarId, Value>,
     pub predecls: HashMap<Vec<u8>, DeclId>, // temporary storage for predeclarations
-    pub decls: HashMap<(Vec<u8>, Type), DeclId>,
-    pub modules: HashMap<Vec<u8>, ModuleId>,
-    pub visibility: Visibility,
+    decls: HashMap<(Vec<u8>, Type), DeclId>,
+    modules: HashMap<Vec<u8>, ModuleId>,
+    visibility: Visibility,
     pub origin: ModuleId, // The original module the overlay was created from
     pub prefixed: bool,   // Whether the overlay has definitions prefixed with its name
+
+    /// Everything that has changed relative to `origin` since this overlay was created.
+    change_log: OverlayChanges,
+
+    /// Doc-comment spans for decls, keyed by `DeclId`. Lives alongside `decls` (rather than in
+    /// the engine's separate `Usage` side table) so comments survive a decl being re-exported or
+    /// prefixed across overlays via `overlay use`/`hide`.
+    usages: HashMap<DeclId, Vec<Span>>,
+}
+
+/// Everything an [`OverlayFrame`] has added, overridden, or hidden relative to its `origin`
+/// module since it was created. Powers `overlay use foo`-style introspection: what did this
+/// overlay actually bring into scope, and what did it shadow?
+///
+/// Each field is a set, not a log: repeatedly overriding or hiding the same key records it once,
+/// so tooling computing a clean `overlay hide` (removing exactly the names this overlay
+/// introduced) doesn't have to dedupe the result itself.
+#[derive(Debug, Clone, Default)]
+pub struct OverlayChanges {
+    pub added_decls: HashSet<(Vec<u8>, Type)>,
+    pub overridden_decls: HashSet<(Vec<u8>, Type)>,
+    pub hidden_decls: HashSet<DeclId>,
+    pub added_vars: HashSet<Vec<u8>>,
+    pub overridden_vars: HashSet<Vec<u8>>,
+    pub added_modules: HashSet<Vec<u8>>,
+    pub overridden_modules: HashSet<Vec<u8>>,
+    pub added_constants: HashSet<VarId>,
+    pub overridden_constants: HashSet<VarId>,
 }
 
 impl OverlayFrame {
@@ -199,11 +791,37 @@ impl OverlayFrame {
             visibility: Visibility::new(),
             origin,
             prefixed,
+            change_log: OverlayChanges::default(),
+            usages: HashMap::new(),
         }
     }
 
-    pub fn insert_decl(&mut self, name: Vec<u8>, input: Type, decl_id: DeclId) -> Option<DeclId> {
-        self.decls.insert((name, input), decl_id)
+    /// Insert a decl, returning the prior `DeclId` for this key (if any) and the prior `usages`
+    /// entry for `decl_id` (if any). `usages` is always set to match `comments` — including
+    /// clearing it when `comments` is `None` — so a `DeclId` reused across a parser retry never
+    /// surfaces a stale doc comment from a previous attempt.
+    pub fn insert_decl(
+        &mut self,
+        name: Vec<u8>,
+        input: Type,
+        decl_id: DeclId,
+        comments: Option<Vec<Span>>,
+    ) -> (Option<DeclId>, Option<Vec<Span>>) {
+        let key = (name, input);
+        let prior = self.decls.insert(key.clone(), decl_id);
+
+        if prior.is_some() {
+            self.change_log.overridden_decls.insert(key);
+        } else {
+            self.change_log.added_decls.insert(key);
+        }
+
+        let prior_comments = match comments.filter(|comments| !comments.is_empty()) {
+            Some(comments) => self.usages.insert(decl_id, comments),
+            None => self.usages.remove(&decl_id),
+        };
+
+        (prior, prior_comments)
     }
 
     pub fn get_decl(&self, name: &[u8], input: &Type) -> Option<DeclId> {
@@ -213,6 +831,60 @@ impl OverlayFrame {
             self.decls.get(&(name, &Type::Any) as &dyn DeclKey).cloned()
         }
     }
+
+    /// Look up the doc-comment spans recorded for `decl_id` in this overlay. Callers that need
+    /// to respect visibility should resolve a `DeclId` via [`ScopeFrame::get_decl`] first and
+    /// pass it here, rather than resolving by name/input independently of visibility.
+    pub fn get_comments_for_decl_id(&self, decl_id: &DeclId) -> Option<&[Span]> {
+        self.usages.get(decl_id).map(Vec::as_slice)
+    }
+
+    pub fn insert_var(&mut self, name: Vec<u8>, var_id: VarId) -> Option<VarId> {
+        let prior = self.vars.insert(name.clone(), var_id);
+
+        if prior.is_some() {
+            self.change_log.overridden_vars.insert(name);
+        } else {
+            self.change_log.added_vars.insert(name);
+        }
+
+        prior
+    }
+
+    pub fn insert_module(&mut self, name: Vec<u8>, module_id: ModuleId) -> Option<ModuleId> {
+        let prior = self.modules.insert(name.clone(), module_id);
+
+        if prior.is_some() {
+            self.change_log.overridden_modules.insert(name);
+        } else {
+            self.change_log.added_modules.insert(name);
+        }
+
+        prior
+    }
+
+    pub fn insert_constant(&mut self, var_id: VarId, value: Value) -> Option<Value> {
+        let prior = self.constants.insert(var_id, value);
+
+        if prior.is_some() {
+            self.change_log.overridden_constants.insert(var_id);
+        } else {
+            self.change_log.added_constants.insert(var_id);
+        }
+
+        prior
+    }
+
+    pub fn hide_decl_id(&mut self, decl_id: &DeclId) {
+        self.visibility.hide_decl_id(decl_id);
+        self.change_log.hidden_decls.insert(*decl_id);
+    }
+
+    /// Everything this overlay has added, overridden, or hidden relative to `origin` since it
+    /// was created.
+    pub fn changes(&self) -> OverlayChanges {
+        self.change_log.clone()
+    }
 }
 
 trait DeclKey {
@@ -272,3 +944,125 @@ impl Default for ScopeFrame {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nested_rollback_leaves_pre_transaction_entries_untouched() {
+        let mut scope = ScopeFrame::with_empty_overlay(b"test".to_vec(), ModuleId::new(0), false);
+        let overlay_id = 0;
+
+        let before = DeclId::new(1);
+        scope.insert_decl(overlay_id, b"before".to_vec(), Type::Any, before, None);
+
+        scope.start_transaction();
+        scope.insert_decl(overlay_id, b"outer".to_vec(), Type::Any, DeclId::new(2), None);
+
+        scope.start_transaction();
+        scope.insert_decl(overlay_id, b"inner".to_vec(), Type::Any, DeclId::new(3), None);
+        scope.rollback_transaction();
+
+        scope.commit_transaction();
+
+        assert_eq!(scope.get_decl(b"before", &Type::Any), Some(before));
+        assert_eq!(scope.get_decl(b"outer", &Type::Any), Some(DeclId::new(2)));
+        assert_eq!(scope.get_decl(b"inner", &Type::Any), None);
+    }
+
+    #[test]
+    fn outer_rollback_undoes_a_committed_inner_transaction_too() {
+        let mut scope = ScopeFrame::with_empty_overlay(b"test".to_vec(), ModuleId::new(0), false);
+        let overlay_id = 0;
+
+        let before = DeclId::new(1);
+        scope.insert_decl(overlay_id, b"before".to_vec(), Type::Any, before, None);
+
+        scope.start_transaction();
+        scope.insert_decl(overlay_id, b"outer".to_vec(), Type::Any, DeclId::new(2), None);
+
+        scope.start_transaction();
+        scope.insert_decl(overlay_id, b"inner".to_vec(), Type::Any, DeclId::new(3), None);
+        scope.commit_transaction();
+
+        scope.rollback_transaction();
+
+        assert_eq!(scope.get_decl(b"before", &Type::Any), Some(before));
+        assert_eq!(scope.get_decl(b"outer", &Type::Any), None);
+        assert_eq!(scope.get_decl(b"inner", &Type::Any), None);
+    }
+
+    #[test]
+    fn hidden_in_higher_overlay_masks_visible_in_lower() {
+        let mut scope = ScopeFrame::new();
+        scope.overlays.push((
+            b"lower".to_vec(),
+            OverlayFrame::from_origin(ModuleId::new(0), false),
+        ));
+        scope.overlays.push((
+            b"higher".to_vec(),
+            OverlayFrame::from_origin(ModuleId::new(0), false),
+        ));
+
+        let lower_id = 0;
+        let higher_id = 1;
+        scope.activate_overlay(lower_id);
+        scope.activate_overlay(higher_id);
+
+        let decl_id = DeclId::new(1);
+        scope.insert_decl(lower_id, b"foo".to_vec(), Type::Any, decl_id, None);
+        scope.use_decl_id(lower_id, decl_id);
+        scope.hide_decl_id(higher_id, decl_id);
+
+        assert_eq!(scope.get_decl(b"foo", &Type::Any), None);
+    }
+
+    #[test]
+    fn decl_cache_evicts_at_capacity_and_invalidates_on_hide_and_activation() {
+        let mut scope = ScopeFrame::with_empty_overlay(b"test".to_vec(), ModuleId::new(0), false);
+        let overlay_id = 0;
+        scope.set_decl_cache_capacity(1);
+
+        let foo = DeclId::new(1);
+        let bar = DeclId::new(2);
+        scope.insert_decl(overlay_id, b"foo".to_vec(), Type::Any, foo, None);
+        scope.insert_decl(overlay_id, b"bar".to_vec(), Type::Any, bar, None);
+
+        assert_eq!(scope.get_decl_cached(b"foo", &Type::Any), Some(foo));
+        assert_eq!(scope.get_decl_cached(b"bar", &Type::Any), Some(bar));
+        assert!(scope.decl_cache.index.len() <= 1);
+
+        assert!(!scope.decl_cache.index.is_empty());
+        scope.hide_decl_id(overlay_id, bar);
+        assert!(scope.decl_cache.index.is_empty());
+
+        scope.get_decl_cached(b"foo", &Type::Any);
+        assert!(!scope.decl_cache.index.is_empty());
+        scope.overlays.push((
+            b"other".to_vec(),
+            OverlayFrame::from_origin(ModuleId::new(0), false),
+        ));
+        scope.activate_overlay(1);
+        assert!(scope.decl_cache.index.is_empty());
+    }
+
+    #[test]
+    fn comments_do_not_leak_through_a_hidden_decl() {
+        let mut scope = ScopeFrame::with_empty_overlay(b"test".to_vec(), ModuleId::new(0), false);
+        let overlay_id = 0;
+        let decl_id = DeclId::new(1);
+
+        scope.insert_decl(
+            overlay_id,
+            b"foo".to_vec(),
+            Type::Any,
+            decl_id,
+            Some(vec![Span::test_data()]),
+        );
+        assert!(scope.get_decl_comments(b"foo", &Type::Any).is_some());
+
+        scope.hide_decl_id(overlay_id, decl_id);
+        assert_eq!(scope.get_decl_comments(b"foo", &Type::Any), None);
+    }
+}